@@ -7,6 +7,11 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
+mod camera;
+mod model;
+mod post_process;
+mod texture;
+
 #[derive(PartialEq)]
 enum KeyFlag {
     Pressed,
@@ -44,19 +49,21 @@ enum Entities {
 }
 
 struct GameState {
-    keyboard: [bool; 193],
+    keyboard: Arc<Mutex<[bool; 193]>>,
     entities: Vec<Entities>,
     position: Vec<i32>,
     health: Vec<i32>,
+    position_sender: Sender<Vec<i32>>,
 }
 
 impl GameState {
-    fn new() -> Self {
+    fn new(keyboard: Arc<Mutex<[bool; 193]>>, position_sender: Sender<Vec<i32>>) -> Self {
         return Self {
-            keyboard: [false; 193],
-            entities: vec![],
-            position: vec![],
-            health: vec![]
+            keyboard,
+            entities: vec![Entities::Player1, Entities::Player2],
+            position: vec![0, 3],
+            health: vec![],
+            position_sender,
         };
     }
 
@@ -68,44 +75,52 @@ impl GameState {
                     break;
                 }
 
+                let mut keyboard = self.keyboard.lock().unwrap();
+
                 if v.key_flag == KeyFlag::Pressed {
-                    self.keyboard[v.keycode as usize] = true;
+                    keyboard[v.keycode as usize] = true;
                 }
 
                 if v.key_flag == KeyFlag::Released {
-                    self.keyboard[v.keycode as usize] = false;
+                    keyboard[v.keycode as usize] = false;
                 }
             }
 
-            if self.keyboard[KeyCode::KeyW as usize] == true {
-                println!("forward!!");
-            }
+            {
+                // Player1 (entity 0) is the local player and is driven directly
+                // by A/D; Player2 starts elsewhere so the two entities are
+                // visibly distinct until networking fills in a real remote position.
+                let keyboard = self.keyboard.lock().unwrap();
 
-            if self.keyboard[KeyCode::KeyS as usize] == true {
-                println!("backward");
-            }
+                if keyboard[KeyCode::KeyD as usize] {
+                    self.position[0] += 1;
+                }
 
-            if self.keyboard[KeyCode::KeyA as usize] == true {
-                println!("left");
+                if keyboard[KeyCode::KeyA as usize] {
+                    self.position[0] -= 1;
+                }
             }
 
-            if self.keyboard[KeyCode::KeyD as usize] == true {
-                println!("right");
-            }
+            self.position_sender.send(self.position.clone());
+
+            // Without this, the loop has no await point and spins as fast as
+            // the CPU allows, flooding the unbounded position channel far
+            // faster than the render loop drains it (once per frame).
+            tokio::time::sleep(std::time::Duration::from_millis(16)).await;
         }
     }
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
+pub(crate) struct Vertex {
+    pub(crate) position: [f32; 3],
+    pub(crate) tex_coords: [f32; 2],
 }
 
 impl Vertex {
     const ATTRIBS: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -118,19 +133,42 @@ impl Vertex {
     }
 }
 
-const VERTICES: &[Vertex] = &[
-    Vertex { position: [-0.0868241, 0.49240386, 0.0], color: [0.5, 0.0, 0.5] }, // A
-    Vertex { position: [-0.49513406, 0.06958647, 0.0], color: [0.5, 0.0, 0.5] }, // B
-    Vertex { position: [-0.21918549, -0.44939706, 0.0], color: [0.5, 0.0, 0.5] }, // C
-    Vertex { position: [0.35966998, -0.3473291, 0.0], color: [0.5, 0.0, 0.5] }, // D
-    Vertex { position: [0.44147372, 0.2347359, 0.0], color: [0.5, 0.0, 0.5] }, // E
-];
+struct Instance {
+    position: cgmath::Vector3<f32>,
+}
+
+impl Instance {
+    fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: cgmath::Matrix4::from_translation(self.position).into(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceRaw {
+    model: [[f32; 4]; 4],
+}
+
+impl InstanceRaw {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+    ];
 
-const INDICES: &[u16] = &[
-    0, 1, 4,
-    1, 2, 4,
-    2, 3, 4,
-];
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
 
 struct GraphicEngine<'a> {
     surface: wgpu::Surface<'a>,
@@ -140,14 +178,24 @@ struct GraphicEngine<'a> {
     size: winit::dpi::PhysicalSize<u32>,
     window: &'a Window,
     pipeline: wgpu::RenderPipeline,
-    buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-    num_vertices: u32, 
+    model: model::Model,
+    depth_texture: texture::Texture,
+    camera: camera::Camera,
+    camera_uniform: camera::CameraUniform,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    keyboard: Arc<Mutex<[bool; 193]>>,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: u32,
+    num_instances: u32,
+    scene_texture: texture::Texture,
+    ping_pong: [texture::Texture; 2],
+    post_bind_group_layout: wgpu::BindGroupLayout,
+    post_passes: Vec<post_process::PostProcessPass>,
 }
 
 impl<'a> GraphicEngine<'a> {
-    async fn new(window: &'a Window) -> GraphicEngine<'a> {
+    async fn new(window: &'a Window, keyboard: Arc<Mutex<[bool; 193]>>) -> GraphicEngine<'a> {
         let size = window.inner_size();
         // Instance corresponds to WebGPU's GPU object.
         // We specify the backend options we want in the instance descriptor, 
@@ -201,12 +249,62 @@ impl<'a> GraphicEngine<'a> {
             desired_maximum_frame_latency: 2
         };
 
+        let texture_bind_group_layout = texture::Texture::bind_group_layout(&device);
+
+        let camera = camera::Camera {
+            eye: (0.0, 1.0, 2.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: config.width as f32 / config.height as f32,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        };
+
+        let mut camera_uniform = camera::CameraUniform::new();
+        camera_uniform.update_view_proj(&camera);
+
+        let camera_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        let camera_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: Some("camera_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            }
+        );
+
+        let camera_bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                label: Some("camera_bind_group"),
+                layout: &camera_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                }],
+            }
+        );
+
         let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
 
         let pipeline_layout = device.create_pipeline_layout(
-            &wgpu::PipelineLayoutDescriptor { 
-                label: Some("Render Pipeline Layout"), 
-                bind_group_layouts: &[], 
+            &wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout, &texture_bind_group_layout],
                 push_constant_ranges: &[]
             }
         );
@@ -220,6 +318,7 @@ impl<'a> GraphicEngine<'a> {
                     entry_point: "vs_main",
                     buffers: &[
                         Vertex::desc(),
+                        InstanceRaw::desc(),
                     ]
                 },
                 fragment: Some(wgpu::FragmentState { 
@@ -241,8 +340,14 @@ impl<'a> GraphicEngine<'a> {
                     conservative: false,
                 },
 
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState { 
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
                     count: 1, 
                     mask: !0, 
                     alpha_to_coverage_enabled: false,
@@ -251,24 +356,35 @@ impl<'a> GraphicEngine<'a> {
             }
         );
 
-        let buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Buffer"),
-                contents: bytemuck::cast_slice(VERTICES),
-                usage: wgpu::BufferUsages::VERTEX
-            }
-        );
+        let model = model::load_model("quad.obj", &device, &queue, &texture_bind_group_layout);
+
+        let depth_texture = texture::Texture::create_depth_texture(&device, &config, "depth_texture");
 
-        let index_buffer = device.create_buffer_init(
+        let instances = vec![Instance { position: cgmath::Vector3::new(0.0, 0.0, 0.0) }];
+        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        let instance_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(INDICES),
-                usage: wgpu::BufferUsages::INDEX,
+                label: Some("Instance Buffer"),
+                contents: bytemuck::cast_slice(&instance_data),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             }
         );
-
-        let num_vertices = VERTICES.len() as u32;
-        let num_indices = INDICES.len() as u32;
+        let num_instances = instances.len() as u32;
+        let instance_capacity = num_instances;
+
+        let scene_texture = texture::Texture::create_render_target(&device, &config, "scene_texture");
+        let ping_pong = [
+            texture::Texture::create_render_target(&device, &config, "post_process_ping"),
+            texture::Texture::create_render_target(&device, &config, "post_process_pong"),
+        ];
+        let post_bind_group_layout = texture::Texture::bind_group_layout(&device);
+        let post_passes = vec![post_process::PostProcessPass::new(
+            &device,
+            config.format,
+            &post_bind_group_layout,
+            include_str!("post_process.wgsl"),
+            "Post Process Pass",
+        )];
 
         return GraphicEngine {
             surface,
@@ -278,12 +394,48 @@ impl<'a> GraphicEngine<'a> {
             size,
             window,
             pipeline,
-            buffer,
-            index_buffer,
-            num_indices,
-            num_vertices,
+            model,
+            depth_texture,
+            camera,
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+            keyboard,
+            instance_buffer,
+            instance_capacity,
+            num_instances,
+            scene_texture,
+            ping_pong,
+            post_bind_group_layout,
+            post_passes,
         };
     }
+
+    // Updates the instance buffer from GameState's latest entity positions,
+    // received over the position channel each frame. Only reallocates when
+    // the entity count outgrows the buffer; otherwise this is just a
+    // queue.write_buffer, since this runs once per redrawn frame.
+    fn update_instances(&mut self, positions: &[i32]) {
+        let instances = positions
+            .iter()
+            .map(|&x| Instance { position: cgmath::Vector3::new(x as f32, 0.0, 0.0) })
+            .collect::<Vec<_>>();
+        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        self.num_instances = instance_data.len() as u32;
+
+        if self.num_instances > self.instance_capacity {
+            self.instance_buffer = self.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Instance Buffer"),
+                    contents: bytemuck::cast_slice(&instance_data),
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                }
+            );
+            self.instance_capacity = self.num_instances;
+        } else {
+            self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+        }
+    }
     
     // This function is called whenever a change in size is detected from
     // the window events.
@@ -293,8 +445,14 @@ impl<'a> GraphicEngine<'a> {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
-
-            use image::GenericImageView;
+            self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.camera.aspect = self.config.width as f32 / self.config.height as f32;
+
+            self.scene_texture = texture::Texture::create_render_target(&self.device, &self.config, "scene_texture");
+            self.ping_pong = [
+                texture::Texture::create_render_target(&self.device, &self.config, "post_process_ping"),
+                texture::Texture::create_render_target(&self.device, &self.config, "post_process_pong"),
+            ];
         }
     }
 
@@ -307,7 +465,39 @@ impl<'a> GraphicEngine<'a> {
     }
 
     fn update(&mut self) {
+        use cgmath::InnerSpace;
+
+        const SPEED: f32 = 0.05;
+
+        let forward = (self.camera.target - self.camera.eye).normalize();
+        let right = forward.cross(self.camera.up).normalize();
+        let mut translation = cgmath::Vector3::new(0.0, 0.0, 0.0);
+
+        {
+            let keyboard = self.keyboard.lock().unwrap();
+
+            if keyboard[KeyCode::KeyW as usize] {
+                translation += forward * SPEED;
+            }
 
+            if keyboard[KeyCode::KeyS as usize] {
+                translation -= forward * SPEED;
+            }
+
+            if keyboard[KeyCode::KeyD as usize] {
+                translation += right * SPEED;
+            }
+
+            if keyboard[KeyCode::KeyA as usize] {
+                translation -= right * SPEED;
+            }
+        }
+
+        self.camera.eye += translation;
+        self.camera.target += translation;
+
+        self.camera_uniform.update_view_proj(&self.camera);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -324,7 +514,7 @@ impl<'a> GraphicEngine<'a> {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: &self.scene_texture.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -336,14 +526,48 @@ impl<'a> GraphicEngine<'a> {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
                 occlusion_query_set: None,
                 timestamp_writes: None
             });
             render_pass.set_pipeline(&self.pipeline);
-            render_pass.set_vertex_buffer(0, self.buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+
+            for mesh in &self.model.meshes {
+                let material = &self.model.materials[mesh.material];
+                render_pass.set_bind_group(1, &material.bind_group, &[]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..mesh.num_elements, 0, 0..self.num_instances);
+            }
+        }
+
+        // Post-processing: ping-pong the scene through each configured pass,
+        // so pass N's output becomes pass N+1's sampled input. The final pass
+        // writes into the swapchain view so the scene pass stays decoupled
+        // from presentation.
+        let mut input = &self.scene_texture;
+        let mut ping_index = 0;
+
+        for (i, pass) in self.post_passes.iter().enumerate() {
+            let is_last = i == self.post_passes.len() - 1;
+            let output_view = if is_last { &view } else { &self.ping_pong[ping_index].view };
+            let input_bind_group = input.bind_group(&self.device, &self.post_bind_group_layout);
+
+            pass.draw(&mut encoder, &input_bind_group, output_view);
+
+            if !is_last {
+                input = &self.ping_pong[ping_index];
+                ping_index = 1 - ping_index;
+            }
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -363,9 +587,12 @@ async fn main() {
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
     let (tx, mut rx) = mpsc::channel();
+    let (position_tx, position_rx) = mpsc::channel();
+    let keyboard = Arc::new(Mutex::new([false; 193]));
 
+    let game_keyboard = keyboard.clone();
     tokio::spawn(async move {
-        let mut game_state = GameState::new();
+        let mut game_state = GameState::new(game_keyboard, position_tx);
         game_state.run(&mut rx)
         .await
     });
@@ -373,8 +600,9 @@ async fn main() {
     // Graphics section starts here
 
     let mut surface_configured = false;
+    let mut entity_positions: Vec<i32> = vec![];
 
-    let mut state = GraphicEngine::new(&window).await;
+    let mut state = GraphicEngine::new(&window, keyboard).await;
     event_loop.run(move |event, control_flow| match event {
         Event::WindowEvent {ref event, window_id} if window_id == state.window.id() => {
             match event {
@@ -391,6 +619,11 @@ async fn main() {
                         return;
                     }
 
+                    while let Ok(positions) = position_rx.try_recv() {
+                        entity_positions = positions;
+                    }
+                    state.update_instances(&entity_positions);
+
                     state.update();
                     match state.render() {
                         Ok(_) => {},
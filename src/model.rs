@@ -0,0 +1,118 @@
+use wgpu::util::DeviceExt;
+
+use crate::texture;
+use crate::Vertex as MeshVertex;
+
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: texture::Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+    pub material: usize,
+}
+
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+pub fn load_model(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+) -> Model {
+    let res_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res");
+    let obj_path = res_dir.join(file_name);
+
+    let (obj_models, obj_materials) = tobj::load_obj(
+        &obj_path,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+    ).unwrap();
+
+    let obj_materials = obj_materials.unwrap();
+
+    let mut materials = obj_materials
+        .into_iter()
+        .map(|mat| {
+            let diffuse_path = res_dir.join(&mat.diffuse_texture);
+            let diffuse_bytes = std::fs::read(diffuse_path).unwrap();
+            let diffuse_texture = texture::Texture::from_bytes(device, queue, &diffuse_bytes, &mat.diffuse_texture);
+            let bind_group = diffuse_texture.bind_group(device, texture_bind_group_layout);
+
+            Material {
+                name: mat.name,
+                diffuse_texture,
+                bind_group,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // An .obj with no mtllib (or a material missing map_Kd) leaves this
+    // empty; fall back to a placeholder material so mesh.material always
+    // indexes something real instead of panicking in render().
+    if materials.is_empty() {
+        let placeholder_bytes = include_bytes!("../res/happy-tree.png");
+        let placeholder_texture = texture::Texture::from_bytes(device, queue, placeholder_bytes, "placeholder");
+        let bind_group = placeholder_texture.bind_group(device, texture_bind_group_layout);
+
+        materials.push(Material {
+            name: "placeholder".to_string(),
+            diffuse_texture: placeholder_texture,
+            bind_group,
+        });
+    }
+
+    let meshes = obj_models
+        .into_iter()
+        .map(|model| {
+            let has_texcoords = !model.mesh.texcoords.is_empty();
+
+            let vertices = (0..model.mesh.positions.len() / 3)
+                .map(|i| MeshVertex {
+                    position: [
+                        model.mesh.positions[i * 3],
+                        model.mesh.positions[i * 3 + 1],
+                        model.mesh.positions[i * 3 + 2],
+                    ],
+                    tex_coords: if has_texcoords {
+                        [model.mesh.texcoords[i * 2], model.mesh.texcoords[i * 2 + 1]]
+                    } else {
+                        [0.0, 0.0]
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", file_name)),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", file_name)),
+                contents: bytemuck::cast_slice(&model.mesh.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+            Mesh {
+                name: model.name,
+                vertex_buffer,
+                index_buffer,
+                num_elements: model.mesh.indices.len() as u32,
+                material: model.mesh.material_id.unwrap_or(0).min(materials.len() - 1),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Model { meshes, materials }
+}